@@ -7,20 +7,34 @@
 //!
 
 extern crate toml;
+extern crate serde_json;
+extern crate serde_yaml;
 
 use std::fs::File;
 use std::io::BufReader;
 use std::io::prelude::Read;
 use std::io;
-use std::path::PathBuf;
+use std::env;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
 use std::fmt;
 
 use config::Config;
-use repository::{Mirror, Repository};
+use repository::{Mirror, MirrorAuth, Repository};
 
 pub(crate) enum ParseConfError {
     Io(io::Error),
     Deserialize(toml::de::Error),
+    /// A JSON or YAML document that could not be decoded into the common value model.
+    Format { format: &'static str, message: String },
+    /// A table key Nest does not recognize (strict mode only).
+    UnknownKey(String),
+    /// A key whose value has the wrong type (strict mode only).
+    TypeError {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
     Str(String),
 }
 
@@ -29,19 +43,93 @@ impl fmt::Display for ParseConfError {
         match *self {
             ParseConfError::Io(ref err) => write!(f, "{}", err),
             ParseConfError::Deserialize(ref err) => write!(f, "{}", err),
+            ParseConfError::Format {
+                format,
+                ref message,
+            } => write!(f, "invalid {} config: {}", format, message),
+            ParseConfError::UnknownKey(ref key) => write!(f, "unknown config key: {}", key),
+            ParseConfError::TypeError {
+                ref key,
+                expected,
+                found,
+            } => write!(f, "{} expected {}, found {}", key, expected, found),
             ParseConfError::Str(ref err) => write!(f, "{}", err),
         }
     }
 }
 
-/// A struct holding the TOML main value
+/// The serialization format a Nest config file is written in.
+///
+/// The format is picked from the file extension and falls back to sniffing the
+/// content when the extension is missing or unknown. JSON and YAML documents are
+/// normalized into `toml::value::Value` so the rest of the parser stays format
+/// agnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Format {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Guesses the format from a file extension, if it is one we know about.
+    fn from_path(path: &Path) -> Option<Format> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => Some(Format::Toml),
+            Some("json") => Some(Format::Json),
+            Some("yaml") | Some("yml") => Some(Format::Yaml),
+            _ => None,
+        }
+    }
+
+    /// Sniffs the format from the document's content as a last resort.
+    ///
+    /// Only a leading `{` is an unambiguous JSON token: `[` opens a TOML table
+    /// header (`[repositories.core]`) just as often as a JSON array, so we treat
+    /// it as TOML. Everything else also defaults to TOML, Nest's native format.
+    fn sniff(content: &str) -> Format {
+        if content.trim_start().starts_with('{') {
+            Format::Json
+        } else {
+            Format::Toml
+        }
+    }
+
+    /// The human-readable name carried in `ParseConfError::Format`.
+    fn name(self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+        }
+    }
+
+    /// Decodes the document into the common `toml::value::Value` model.
+    fn parse(self, content: &str) -> Result<toml::Value, ParseConfError> {
+        match self {
+            Format::Toml => content
+                .parse::<toml::Value>()
+                .map_err(ParseConfError::Deserialize),
+            Format::Json => serde_json::from_str(content).map_err(|e| ParseConfError::Format {
+                format: self.name(),
+                message: e.to_string(),
+            }),
+            Format::Yaml => serde_yaml::from_str(content).map_err(|e| ParseConfError::Format {
+                format: self.name(),
+                message: e.to_string(),
+            }),
+        }
+    }
+}
+
+/// A struct holding the config's main value, normalized to the TOML model
 #[derive(Debug)]
 pub(crate) struct ConfigParser {
     toml: toml::value::Value,
 }
 
 impl ConfigParser {
-    /// Creates a ConfigParser instance from a TOML file
+    /// Creates a ConfigParser instance from a config file (TOML, JSON or YAML)
     ///
     /// self.toml is considered safe to cast to a table after this
     /// ```
@@ -54,13 +142,79 @@ impl ConfigParser {
                 if conf.is_table() {
                     Ok(ConfigParser { toml: conf })
                 } else {
-                    Err(ParseConfError::Str("Invalid toml file".to_string()))
+                    Err(ParseConfError::Str("Invalid config file".to_string()))
                 }
             }
             Err(e) => Err(e),
         }
     }
 
+    /// Walks upward from `start` collecting every `nest.toml`, ordered from the
+    /// farthest in-scope ancestor down to the nearest one.
+    ///
+    /// Discovery is scoped to the user's home directory: it stops once it
+    /// reaches `$HOME` or the filesystem root, whichever comes first — the same
+    /// way Cargo and rustfmt scope their per-project configuration. A
+    /// system-wide file above home (e.g. `/etc/nest.toml`) is therefore out of
+    /// scope and not collected. The returned parsers are meant to be fed to
+    /// `load_layered`, which lets the closer files override the farther ones.
+    pub(crate) fn discover(start: &Path) -> Result<Vec<ConfigParser>, ParseConfError> {
+        let home = env::var_os("HOME").map(PathBuf::from);
+        ConfigParser::discover_until(start, home.as_ref().map(PathBuf::as_path))
+    }
+
+    /// Discovery core with an explicit stop directory, so it can be exercised
+    /// without depending on the caller's home directory.
+    fn discover_until(
+        start: &Path,
+        root: Option<&Path>,
+    ) -> Result<Vec<ConfigParser>, ParseConfError> {
+        const CONFIG_NAME: &str = "nest.toml";
+        let mut parsers = Vec::new();
+        let mut dir = Some(start);
+        while let Some(current) = dir {
+            let candidate = current.join(CONFIG_NAME);
+            if candidate.is_file() {
+                let path = candidate.to_str().ok_or_else(|| {
+                    ParseConfError::Str(format!("non-utf8 config path: {}", candidate.display()))
+                })?;
+                parsers.push(ConfigParser::new(path)?);
+            }
+            if root.map_or(false, |r| current == r) {
+                break;
+            }
+            dir = current.parent();
+        }
+        // Collected nearest-first while walking up; flip so callers apply them
+        // from the outermost file down to the innermost one.
+        parsers.reverse();
+        Ok(parsers)
+    }
+
+    /// Applies a stack of parsers in order so the innermost file wins.
+    ///
+    /// `parsers` is expected to be ordered from the outermost to the nearest
+    /// file (as returned by `discover`). `paths` are overwritten on each layer,
+    /// leaving the nearest value in place, while `repositories` tables are
+    /// unioned by name: the nearest file's mirror list replaces a farther one
+    /// for the same repository.
+    pub(crate) fn load_layered(parsers: &[ConfigParser], conf: &mut Config) {
+        let mut repos: BTreeMap<String, Repository> = BTreeMap::new();
+        for parser in parsers {
+            parser.parse_paths_mut(conf);
+            if let Some(table) = parser.get_table("repositories") {
+                for (key, value) in table {
+                    if let Some(repo) = parser.parse_repo(key, value, conf) {
+                        repos.insert(key.clone(), repo);
+                    }
+                }
+            }
+        }
+        if !repos.is_empty() {
+            conf.set_repositories(repos.into_iter().map(|(_, repo)| repo).collect());
+        }
+    }
+
     /// Replaces the default values in the Config instance with the ones found in the TOML file
     #[inline]
     pub(crate) fn load_to_config(&self, conf: &mut Config) {
@@ -70,6 +224,177 @@ impl ConfigParser {
         }
     }
 
+    /// Strict counterpart of `load_to_config` reporting actionable errors
+    /// instead of silently dropping the offending entry.
+    ///
+    /// Unknown top-level keys, unknown keys inside `paths`/`repositories`, and
+    /// values of the wrong type all abort with a `ParseConfError` pointing at
+    /// the exact key, so a typo'd `mirorrs` no longer turns into an empty repo
+    /// list. The lenient `load_to_config` stays available for backward
+    /// compatibility.
+    pub(crate) fn load_to_config_strict(&self, conf: &mut Config) -> Result<(), ParseConfError> {
+        let table = self.toml.as_table().unwrap();
+        for key in table.keys() {
+            match key.as_str() {
+                "paths" | "repositories" => {}
+                other => return Err(ParseConfError::UnknownKey(other.to_string())),
+            }
+        }
+        self.parse_paths_strict(conf)?;
+        if let Some(repos) = self.parse_repositories_strict(conf)? {
+            conf.set_repositories(repos);
+        }
+        Ok(())
+    }
+
+    fn parse_paths_strict(&self, conf: &mut Config) -> Result<(), ParseConfError> {
+        let table = self.toml.as_table().unwrap();
+        let paths = match table.get("paths") {
+            Some(value) => value.as_table().ok_or_else(|| ParseConfError::TypeError {
+                key: "paths".to_string(),
+                expected: "table",
+                found: value.type_str(),
+            })?,
+            None => return Ok(()),
+        };
+        for (key, value) in paths {
+            let setter: fn(&mut Config, PathBuf) = match key.as_str() {
+                "cache_dir" => Config::set_cache,
+                "download_dir" => Config::set_download_path,
+                other => return Err(ParseConfError::UnknownKey(format!("paths.{}", other))),
+            };
+            let string = value.as_str().ok_or_else(|| ParseConfError::TypeError {
+                key: format!("paths.{}", key),
+                expected: "string",
+                found: value.type_str(),
+            })?;
+            setter(conf, PathBuf::from(string));
+        }
+        Ok(())
+    }
+
+    fn parse_repositories_strict(
+        &self,
+        conf: &Config,
+    ) -> Result<Option<Vec<Repository>>, ParseConfError> {
+        let table = self.toml.as_table().unwrap();
+        let repositories = match table.get("repositories") {
+            Some(value) => value.as_table().ok_or_else(|| ParseConfError::TypeError {
+                key: "repositories".to_string(),
+                expected: "table",
+                found: value.type_str(),
+            })?,
+            None => return Ok(None),
+        };
+        let mut repo_vec = Vec::with_capacity(repositories.len());
+        for (key, value) in repositories {
+            repo_vec.push(self.parse_repo_strict(key, value, conf)?);
+        }
+        Ok(Some(repo_vec))
+    }
+
+    fn parse_repo_strict(
+        &self,
+        repo_name: &str,
+        value: &toml::value::Value,
+        conf: &Config,
+    ) -> Result<Repository, ParseConfError> {
+        let table = value.as_table().ok_or_else(|| ParseConfError::TypeError {
+            key: format!("repositories.{}", repo_name),
+            expected: "table",
+            found: value.type_str(),
+        })?;
+        for key in table.keys() {
+            if key != "mirrors" {
+                return Err(ParseConfError::UnknownKey(format!(
+                    "repositories.{}.{}",
+                    repo_name, key
+                )));
+            }
+        }
+        let mirrors_value = table
+            .get("mirrors")
+            .ok_or_else(|| ParseConfError::TypeError {
+                key: format!("repositories.{}.mirrors", repo_name),
+                expected: "array of strings or mirror tables",
+                found: "nothing",
+            })?;
+        let mirror_list = mirrors_value
+            .as_array()
+            .ok_or_else(|| ParseConfError::TypeError {
+                key: format!("repositories.{}.mirrors", repo_name),
+                expected: "array of strings or mirror tables",
+                found: mirrors_value.type_str(),
+            })?;
+        let mut repo = Repository::new(conf, repo_name);
+        for (idx, mirror) in mirror_list.iter().enumerate() {
+            let key = format!("repositories.{}.mirrors[{}]", repo_name, idx);
+            repo.mirrors_mut()
+                .push(ConfigParser::parse_mirror_strict(&key, mirror)?);
+        }
+        Ok(repo)
+    }
+
+    /// Applies environment-variable overrides on top of an already parsed Config.
+    ///
+    /// Following Cargo's config model, any key can be overridden from the
+    /// environment: `NEST_CACHE_DIR` and `NEST_DOWNLOAD_DIR` replace the
+    /// corresponding paths, and `NEST_REPO_<NAME>_MIRRORS` (comma-separated)
+    /// replaces the mirror list of the `<name>` repository, creating it if it
+    /// does not exist yet. This is meant to be called after `load_to_config` so
+    /// the environment always wins.
+    pub(crate) fn apply_env(conf: &mut Config) {
+        ConfigParser::apply_env_vars(conf, env::vars());
+    }
+
+    /// `apply_env` core, taking the variables explicitly so it can be tested
+    /// without mutating the real environment.
+    pub(crate) fn apply_env_vars<I>(conf: &mut Config, vars: I)
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        for (key, value) in vars {
+            match key.as_str() {
+                "NEST_CACHE_DIR" => conf.set_cache(PathBuf::from(value)),
+                "NEST_DOWNLOAD_DIR" => conf.set_download_path(PathBuf::from(value)),
+                _ => {
+                    if let Some(name) = ConfigParser::env_repo_name(&key) {
+                        let mirrors = value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|url| !url.is_empty())
+                            .map(|url| Mirror::new(url, None, true, None))
+                            .collect();
+                        ConfigParser::set_repo_mirrors(conf, &name, mirrors);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Extracts `<name>` from a `NEST_REPO_<NAME>_MIRRORS` variable, lowercased
+    /// to match the repository names used in the `repositories` table.
+    fn env_repo_name(key: &str) -> Option<String> {
+        let inner = key.strip_prefix("NEST_REPO_")?.strip_suffix("_MIRRORS")?;
+        if inner.is_empty() {
+            None
+        } else {
+            Some(inner.to_lowercase())
+        }
+    }
+
+    /// Replaces the mirror list of `name`, appending a fresh repository when no
+    /// entry with that name exists yet.
+    fn set_repo_mirrors(conf: &mut Config, name: &str, mirrors: Vec<Mirror>) {
+        if let Some(repo) = conf.repositories_mut().iter_mut().find(|r| r.name() == name) {
+            *repo.mirrors_mut() = mirrors;
+            return;
+        }
+        let mut repo = Repository::new(conf, name);
+        *repo.mirrors_mut() = mirrors;
+        conf.repositories_mut().push(repo);
+    }
+
     #[inline]
     fn parse_paths_mut(&self, conf: &mut Config) {
         if let Some(paths) = self.get_table("paths") {
@@ -103,11 +428,166 @@ impl ConfigParser {
         let mirror_list = value.get("mirrors")?.as_array()?;
         let mut repo = Repository::new(conf, repo_name);
         for mirror in mirror_list {
-            repo.mirrors_mut().push(Mirror::new(mirror.as_str()?));
+            if let Some(mirror) = ConfigParser::parse_mirror(mirror) {
+                repo.mirrors_mut().push(mirror);
+            }
         }
         Some(repo)
     }
 
+    /// Reads a single mirror entry, which may be a bare URL string or an inline
+    /// table carrying `url`, `priority`, `enabled` and `auth` fields.
+    fn parse_mirror(value: &toml::value::Value) -> Option<Mirror> {
+        if let Some(url) = value.as_str() {
+            return Some(Mirror::new(url, None, true, None));
+        }
+        let table = value.as_table()?;
+        let url = table.get("url")?.as_str()?;
+        let priority = table.get("priority").and_then(|v| v.as_integer());
+        let enabled = table
+            .get("enabled")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(true);
+        let auth = table.get("auth").and_then(ConfigParser::parse_mirror_auth);
+        Some(Mirror::new(url, priority, enabled, auth))
+    }
+
+    /// Strict counterpart of `parse_mirror`: an inline mirror table must carry a
+    /// `url` and may only contain keys Nest understands, so a typo'd sub-key
+    /// (`priorty`) or a malformed `auth` table surfaces as a `ParseConfError`
+    /// instead of being silently dropped.
+    fn parse_mirror_strict(
+        key: &str,
+        value: &toml::value::Value,
+    ) -> Result<Mirror, ParseConfError> {
+        if let Some(url) = value.as_str() {
+            return Ok(Mirror::new(url, None, true, None));
+        }
+        let table = value.as_table().ok_or_else(|| ParseConfError::TypeError {
+            key: key.to_string(),
+            expected: "string or mirror table",
+            found: value.type_str(),
+        })?;
+        for sub in table.keys() {
+            match sub.as_str() {
+                "url" | "priority" | "enabled" | "auth" => {}
+                other => return Err(ParseConfError::UnknownKey(format!("{}.{}", key, other))),
+            }
+        }
+        let url_value = table.get("url").ok_or_else(|| ParseConfError::TypeError {
+            key: format!("{}.url", key),
+            expected: "string",
+            found: "nothing",
+        })?;
+        let url = url_value.as_str().ok_or_else(|| ParseConfError::TypeError {
+            key: format!("{}.url", key),
+            expected: "string",
+            found: url_value.type_str(),
+        })?;
+        let priority = match table.get("priority") {
+            Some(v) => Some(v.as_integer().ok_or_else(|| ParseConfError::TypeError {
+                key: format!("{}.priority", key),
+                expected: "integer",
+                found: v.type_str(),
+            })?),
+            None => None,
+        };
+        let enabled = match table.get("enabled") {
+            Some(v) => v.as_bool().ok_or_else(|| ParseConfError::TypeError {
+                key: format!("{}.enabled", key),
+                expected: "boolean",
+                found: v.type_str(),
+            })?,
+            None => true,
+        };
+        let auth = match table.get("auth") {
+            Some(v) => Some(ConfigParser::parse_mirror_auth_strict(
+                &format!("{}.auth", key),
+                v,
+            )?),
+            None => None,
+        };
+        Ok(Mirror::new(url, priority, enabled, auth))
+    }
+
+    /// Reads a mirror's `auth` table into a `MirrorAuth`, supporting a token, a
+    /// basic username/password pair, or an `env` reference to a variable
+    /// holding the credentials.
+    fn parse_mirror_auth(value: &toml::value::Value) -> Option<MirrorAuth> {
+        let table = value.as_table()?;
+        if let Some(var) = table.get("env").and_then(|v| v.as_str()) {
+            return Some(MirrorAuth::Env(var.to_string()));
+        }
+        if let Some(token) = table.get("token").and_then(|v| v.as_str()) {
+            return Some(MirrorAuth::Token(token.to_string()));
+        }
+        let username = table.get("username")?.as_str()?.to_string();
+        let password = table.get("password")?.as_str()?.to_string();
+        Some(MirrorAuth::Basic { username, password })
+    }
+
+    /// Strict counterpart of `parse_mirror_auth`, rejecting unknown keys and
+    /// incomplete credential tables instead of returning `None`. Like the
+    /// lenient version, `env` and `token` take precedence over a basic
+    /// username/password pair.
+    fn parse_mirror_auth_strict(
+        key: &str,
+        value: &toml::value::Value,
+    ) -> Result<MirrorAuth, ParseConfError> {
+        let table = value.as_table().ok_or_else(|| ParseConfError::TypeError {
+            key: key.to_string(),
+            expected: "table",
+            found: value.type_str(),
+        })?;
+        for sub in table.keys() {
+            match sub.as_str() {
+                "env" | "token" | "username" | "password" => {}
+                other => return Err(ParseConfError::UnknownKey(format!("{}.{}", key, other))),
+            }
+        }
+        if let Some(var) = table.get("env") {
+            let var = var.as_str().ok_or_else(|| ParseConfError::TypeError {
+                key: format!("{}.env", key),
+                expected: "string",
+                found: var.type_str(),
+            })?;
+            return Ok(MirrorAuth::Env(var.to_string()));
+        }
+        if let Some(token) = table.get("token") {
+            let token = token.as_str().ok_or_else(|| ParseConfError::TypeError {
+                key: format!("{}.token", key),
+                expected: "string",
+                found: token.type_str(),
+            })?;
+            return Ok(MirrorAuth::Token(token.to_string()));
+        }
+        let username = ConfigParser::require_str(table, key, "username")?;
+        let password = ConfigParser::require_str(table, key, "password")?;
+        Ok(MirrorAuth::Basic { username, password })
+    }
+
+    /// Reads a required string field from a credential table, reporting a
+    /// precise `TypeError` when it is missing or of the wrong type.
+    fn require_str(
+        table: &toml::value::Table,
+        key: &str,
+        field: &str,
+    ) -> Result<String, ParseConfError> {
+        let value = table.get(field).ok_or_else(|| ParseConfError::TypeError {
+            key: format!("{}.{}", key, field),
+            expected: "string",
+            found: "nothing",
+        })?;
+        value
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| ParseConfError::TypeError {
+                key: format!("{}.{}", key, field),
+                expected: "string",
+                found: value.type_str(),
+            })
+    }
+
     /// Returns a new list of repositories read from the TOML file
     fn parse_repositories(&self, conf: &Config) -> Option<Vec<Repository>> {
         let repositories = self.get_table("repositories")?;
@@ -137,12 +617,197 @@ impl ConfigParser {
                 if let Err(e) = file_reader.read_to_string(&mut content) {
                     return Err(ParseConfError::Io(e));
                 }
-                match content.parse::<toml::Value>() {
-                    Ok(v) => Ok(v),
-                    Err(e) => Err(ParseConfError::Deserialize(e)),
-                }
+                let format = Format::from_path(Path::new(conf_path))
+                    .unwrap_or_else(|| Format::sniff(&content));
+                format.parse(&content)
             }
             Err(e) => Err(ParseConfError::Io(e)),
         }
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Builds a parser straight from in-memory TOML, skipping the file IO the
+    /// public `new` does so the layering and parsing logic can be exercised on
+    /// its own.
+    fn parser(content: &str) -> ConfigParser {
+        ConfigParser {
+            toml: content.parse::<toml::Value>().expect("valid test toml"),
+        }
+    }
+
+    /// Returns the mirror URLs of the named repository, or `None` if it is absent.
+    fn mirror_urls(conf: &Config, name: &str) -> Option<Vec<String>> {
+        conf.repositories()
+            .iter()
+            .find(|r| r.name() == name)
+            .map(|r| r.mirrors().iter().map(|m| m.url().to_string()).collect())
+    }
+
+    #[test]
+    fn load_layered_applies_outer_then_inner() {
+        // Ordered outermost -> nearest, as `discover` returns them.
+        let outer = parser(
+            "[paths]\ncache_dir = \"/global/cache\"\ndownload_dir = \"/global/dl\"\n\
+             [repositories.core]\nmirrors = [\"https://global/core\"]\n\
+             [repositories.extra]\nmirrors = [\"https://global/extra\"]\n",
+        );
+        let inner = parser(
+            "[paths]\ncache_dir = \"/project/cache\"\n\
+             [repositories.core]\nmirrors = [\"https://project/core\"]\n",
+        );
+        let mut conf = Config::default();
+        ConfigParser::load_layered(&[outer, inner], &mut conf);
+
+        // Nearest `paths` win; an absent key keeps the farther value.
+        assert_eq!(conf.cache(), Path::new("/project/cache"));
+        assert_eq!(conf.download_path(), Path::new("/global/dl"));
+        // `repositories` union by name: nearest mirror list replaces the farther
+        // one, repos only defined farther away survive.
+        assert_eq!(
+            mirror_urls(&conf, "core"),
+            Some(vec!["https://project/core".to_string()])
+        );
+        assert_eq!(
+            mirror_urls(&conf, "extra"),
+            Some(vec!["https://global/extra".to_string()])
+        );
+    }
+
+    #[test]
+    fn discover_until_orders_outer_to_inner_and_stops_at_root() {
+        let root = env::temp_dir().join("nest_discover_until");
+        let nested = root.join("project").join("sub");
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&nested).unwrap();
+        fs::write(root.join("nest.toml"), "[paths]\ncache_dir = \"/r\"\n").unwrap();
+        fs::write(
+            nested.join("nest.toml"),
+            "[paths]\ncache_dir = \"/n\"\n",
+        )
+        .unwrap();
+
+        let parsers = ConfigParser::discover_until(&nested, Some(&root)).unwrap();
+        assert_eq!(parsers.len(), 2);
+        let mut conf = Config::default();
+        ConfigParser::load_layered(&parsers, &mut conf);
+        // Nearest file applied last, so it wins.
+        assert_eq!(conf.cache(), Path::new("/n"));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn apply_env_vars_overrides_paths() {
+        let mut conf = Config::default();
+        conf.set_cache(PathBuf::from("/from/file"));
+        ConfigParser::apply_env_vars(
+            &mut conf,
+            vec![
+                ("NEST_CACHE_DIR".to_string(), "/from/env".to_string()),
+                ("NEST_DOWNLOAD_DIR".to_string(), "/dl/env".to_string()),
+                // Unrelated variables are ignored.
+                ("PATH".to_string(), "/usr/bin".to_string()),
+            ],
+        );
+        assert_eq!(conf.cache(), Path::new("/from/env"));
+        assert_eq!(conf.download_path(), Path::new("/dl/env"));
+    }
+
+    #[test]
+    fn apply_env_vars_replaces_existing_repo_mirrors() {
+        let mut conf = Config::default();
+        let mut repo = Repository::new(&conf, "core");
+        repo.mirrors_mut().push(Mirror::new("https://old", None, true, None));
+        conf.set_repositories(vec![repo]);
+
+        // `<NAME>` is lowercased to match the repository table keys.
+        ConfigParser::apply_env_vars(
+            &mut conf,
+            vec![(
+                "NEST_REPO_CORE_MIRRORS".to_string(),
+                "https://a, https://b".to_string(),
+            )],
+        );
+        assert_eq!(
+            mirror_urls(&conf, "core"),
+            Some(vec!["https://a".to_string(), "https://b".to_string()])
+        );
+    }
+
+    #[test]
+    fn apply_env_vars_creates_missing_repo() {
+        let mut conf = Config::default();
+        ConfigParser::apply_env_vars(
+            &mut conf,
+            vec![(
+                "NEST_REPO_EXTRA_MIRRORS".to_string(),
+                "https://x".to_string(),
+            )],
+        );
+        assert_eq!(
+            mirror_urls(&conf, "extra"),
+            Some(vec!["https://x".to_string()])
+        );
+    }
+
+    #[test]
+    fn strict_rejects_unknown_top_level_key() {
+        let mut conf = Config::default();
+        let err = parser("mirorrs = 1\n")
+            .load_to_config_strict(&mut conf)
+            .unwrap_err();
+        match err {
+            ParseConfError::UnknownKey(ref key) => assert_eq!(key, "mirorrs"),
+            other => panic!("expected UnknownKey, got {}", other),
+        }
+    }
+
+    #[test]
+    fn strict_reports_type_error_for_non_array_mirrors() {
+        let mut conf = Config::default();
+        let err = parser("[repositories.core]\nmirrors = 1\n")
+            .load_to_config_strict(&mut conf)
+            .unwrap_err();
+        match err {
+            ParseConfError::TypeError {
+                ref key, expected, ..
+            } => {
+                assert_eq!(key, "repositories.core.mirrors");
+                assert_eq!(expected, "array of strings or mirror tables");
+            }
+            other => panic!("expected TypeError, got {}", other),
+        }
+    }
+
+    #[test]
+    fn strict_rejects_unknown_mirror_table_key() {
+        let mut conf = Config::default();
+        let err = parser(
+            "[repositories.core]\nmirrors = [{ url = \"https://a\", priorty = 1 }]\n",
+        )
+        .load_to_config_strict(&mut conf)
+        .unwrap_err();
+        match err {
+            ParseConfError::UnknownKey(ref key) => {
+                assert_eq!(key, "repositories.core.mirrors[0].priorty")
+            }
+            other => panic!("expected UnknownKey, got {}", other),
+        }
+    }
+
+    #[test]
+    fn format_selection_prefers_extension_then_sniffs() {
+        assert_eq!(Format::from_path(Path::new("nest.json")), Some(Format::Json));
+        assert_eq!(Format::from_path(Path::new("nest.yml")), Some(Format::Yaml));
+        assert_eq!(Format::from_path(Path::new("nest")), None);
+        // Sniffing: only a leading `{` is JSON, anything else (`[` included)
+        // falls back to TOML.
+        assert_eq!(Format::sniff("  {\"a\": 1}"), Format::Json);
+        assert_eq!(Format::sniff("cache_dir = \"/c\""), Format::Toml);
+    }
 }
\ No newline at end of file